@@ -1,6 +1,7 @@
 mod ns_proof;
 mod ns_table;
 mod payload;
+mod zerocopy;
 
 pub use ns_proof::NsProof;
 pub use ns_table::{NsIndex, NsTable};
@@ -8,3 +9,19 @@ pub use payload::Payload;
 
 pub use ns_table::NsIter;
 pub use payload::PayloadByteLen;
+
+/// Test-only helpers shared by the ns_table and ns_proof test suites, so both
+/// don't carry their own copy of the same wire-encoding fixture.
+#[cfg(test)]
+pub(crate) mod test_support {
+    /// Encodes a namespace table's wire format from `(namespace_id,
+    /// end_offset)` pairs.
+    pub fn encode_table(entries: &[(u32, u32)]) -> Vec<u8> {
+        let mut out = (entries.len() as u32).to_be_bytes().to_vec();
+        for &(namespace_id, end_offset) in entries {
+            out.extend_from_slice(&namespace_id.to_be_bytes());
+            out.extend_from_slice(&end_offset.to_be_bytes());
+        }
+        out
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/nitro/blob/master/LICENSE
+
+//! A proof that a namespace's byte range within a block payload is
+//! consistent with the namespace table and the DA layer's commitment to that
+//! block: a standard Merkle inclusion proof from a `(namespace_id, range)`
+//! leaf up to the block's VID commitment.
+//!
+//! The sibling hashes are parsed with the [`zerocopy`](super::zerocopy)
+//! layer: validated for alignment once, then read in place during
+//! verification.
+
+use std::fmt;
+
+use sha3::{Digest, Keccak256};
+
+use super::{
+    ns_table::NsTable,
+    zerocopy::{FromBytes, Ref},
+};
+
+/// The 32-byte VID/payload commitment a block's namespace table is checked
+/// against.
+pub type Commitment = [u8; 32];
+
+/// A single 32-byte sibling hash in a Merkle proof.
+#[derive(Clone, Copy, Debug)]
+struct Hash32([u8; 32]);
+
+impl FromBytes for Hash32 {
+    const WIDTH: usize = 32;
+
+    fn read(bytes: &[u8]) -> Self {
+        Self(bytes.try_into().unwrap())
+    }
+}
+
+/// A Merkle inclusion proof: the sequence of sibling hashes from a
+/// namespace's leaf up to the commitment root.
+#[derive(Clone, Copy)]
+pub struct NsProof<'a> {
+    siblings: Ref<'a, Hash32>,
+}
+
+#[derive(Debug)]
+pub enum NsProofError {
+    Malformed,
+    NamespaceNotFound,
+    InvalidProof,
+}
+
+impl fmt::Display for NsProofError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NsProofError::Malformed => write!(f, "namespace proof is not a whole number of hashes"),
+            NsProofError::NamespaceNotFound => {
+                write!(f, "namespace not present in namespace table")
+            }
+            NsProofError::InvalidProof => write!(f, "namespace proof did not match the commitment"),
+        }
+    }
+}
+
+impl std::error::Error for NsProofError {}
+
+impl<'a> NsProof<'a> {
+    /// Parses a proof out of its wire encoding: a concatenation of 32-byte
+    /// sibling hashes, ordered leaf to root. The buffer is validated for
+    /// alignment once here; siblings are read directly out of it during
+    /// verification, with no intermediate `Vec`.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, NsProofError> {
+        let siblings = Ref::new(bytes).map_err(|_| NsProofError::Malformed)?;
+        Ok(Self { siblings })
+    }
+
+    /// Verifies this proof against `table` and the block's VID commitment,
+    /// returning the byte range the namespace occupies within the payload.
+    pub fn verify(
+        &self,
+        table: &NsTable,
+        namespace_id: u32,
+        commitment: &Commitment,
+    ) -> Result<(u32, u32), NsProofError> {
+        let index = table.find(namespace_id).ok_or(NsProofError::NamespaceNotFound)?;
+        let (start, end) = table
+            .range(index)
+            .ok_or(NsProofError::NamespaceNotFound)?;
+
+        let mut hash = leaf_hash(namespace_id, start, end);
+        let mut position = index.0 as u64;
+        for sibling in self.siblings.iter() {
+            let mut hasher = Keccak256::new();
+            if position & 1 == 0 {
+                hasher.update(hash);
+                hasher.update(sibling.0);
+            } else {
+                hasher.update(sibling.0);
+                hasher.update(hash);
+            }
+            hash = hasher.finalize().into();
+            position >>= 1;
+        }
+
+        if hash != *commitment {
+            return Err(NsProofError::InvalidProof);
+        }
+        Ok((start, end))
+    }
+}
+
+fn leaf_hash(namespace_id: u32, start: u32, end: u32) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(namespace_id.to_be_bytes());
+    hasher.update(start.to_be_bytes());
+    hasher.update(end.to_be_bytes());
+    hasher.finalize().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::encode_table;
+
+    fn parent_hash(left: [u8; 32], right: [u8; 32]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update(left);
+        hasher.update(right);
+        hasher.finalize().into()
+    }
+
+    #[test]
+    fn verifies_a_two_leaf_proof_against_its_commitment() {
+        let table_bytes = encode_table(&[(5, 10), (7, 25)]);
+        let table = NsTable::parse(&table_bytes).unwrap();
+
+        let leaf0 = leaf_hash(5, 0, 10);
+        let leaf1 = leaf_hash(7, 10, 25);
+        let commitment = parent_hash(leaf0, leaf1);
+
+        let proof = NsProof::parse(&leaf1).unwrap();
+        assert_eq!(
+            proof.verify(&table, 5, &commitment).unwrap(),
+            (0, 10)
+        );
+    }
+
+    #[test]
+    fn rejects_a_proof_that_does_not_match_the_commitment() {
+        let table_bytes = encode_table(&[(5, 10), (7, 25)]);
+        let table = NsTable::parse(&table_bytes).unwrap();
+
+        let leaf0 = leaf_hash(5, 0, 10);
+        let leaf1 = leaf_hash(7, 10, 25);
+        let commitment = parent_hash(leaf0, leaf1);
+
+        // Tamper with the sibling so the recomputed root no longer matches.
+        let mut tampered_sibling = leaf1;
+        tampered_sibling[0] ^= 0xff;
+
+        let proof = NsProof::parse(&tampered_sibling).unwrap();
+        assert!(matches!(
+            proof.verify(&table, 5, &commitment),
+            Err(NsProofError::InvalidProof)
+        ));
+    }
+
+    #[test]
+    fn rejects_a_namespace_absent_from_the_table() {
+        let table_bytes = encode_table(&[(5, 10)]);
+        let table = NsTable::parse(&table_bytes).unwrap();
+        let proof = NsProof::parse(&[]).unwrap();
+        assert!(matches!(
+            proof.verify(&table, 9, &[0; 32]),
+            Err(NsProofError::NamespaceNotFound)
+        ));
+    }
+
+    #[test]
+    fn rejects_a_malformed_proof_buffer() {
+        assert!(matches!(NsProof::parse(&[0; 10]), Err(NsProofError::Malformed)));
+    }
+}
@@ -0,0 +1,168 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/nitro/blob/master/LICENSE
+
+//! The namespace table committed to by Espresso DA: for each namespace
+//! present in a block, it records the namespace id and the byte offset at
+//! which that namespace's data ends within the block payload. A namespace's
+//! byte range is recovered by taking the gap between consecutive offsets.
+//!
+//! Entries are parsed with the [`zerocopy`](super::zerocopy) layer, so a
+//! table is validated once up front and its entries are then read directly
+//! out of the original buffer with no intermediate allocation.
+
+use std::fmt;
+
+use super::zerocopy::{FromBytes, Ref, RefIter, ViewError};
+
+/// Index of a namespace entry within an [`NsTable`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct NsIndex(pub u32);
+
+/// A fixed-width `(namespace_id, end_offset)` record, both big-endian `u32`s.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct NsTableEntry {
+    namespace_id: u32,
+    end_offset: u32,
+}
+
+impl FromBytes for NsTableEntry {
+    const WIDTH: usize = 8;
+
+    fn read(bytes: &[u8]) -> Self {
+        Self {
+            namespace_id: u32::from_be_bytes(bytes[0..4].try_into().unwrap()),
+            end_offset: u32::from_be_bytes(bytes[4..8].try_into().unwrap()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum NsTableError {
+    Truncated,
+    View(ViewError),
+}
+
+impl fmt::Display for NsTableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NsTableError::Truncated => write!(f, "namespace table buffer is too short"),
+            NsTableError::View(err) => write!(f, "namespace table entries: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for NsTableError {}
+
+impl From<ViewError> for NsTableError {
+    fn from(err: ViewError) -> Self {
+        NsTableError::View(err)
+    }
+}
+
+/// A namespace table, validated in place over a borrowed buffer.
+#[derive(Clone, Copy)]
+pub struct NsTable<'a> {
+    entries: Ref<'a, NsTableEntry>,
+}
+
+impl<'a> NsTable<'a> {
+    /// Parses a namespace table out of its wire encoding: a big-endian `u32`
+    /// entry count followed by that many `(namespace_id, end_offset)`
+    /// records, sorted by `end_offset`. The entries are validated for
+    /// alignment and length once here; no further allocation happens on
+    /// lookup or iteration.
+    pub fn parse(bytes: &'a [u8]) -> Result<Self, NsTableError> {
+        if bytes.len() < 4 {
+            return Err(NsTableError::Truncated);
+        }
+        let count = u32::from_be_bytes(bytes[0..4].try_into().unwrap()) as usize;
+        let body = &bytes[4..];
+        if body.len() != count * NsTableEntry::WIDTH {
+            return Err(NsTableError::Truncated);
+        }
+        let entries = Ref::new(body)?;
+        Ok(Self { entries })
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Finds the entry for `namespace_id`, if the table carries one.
+    pub fn find(&self, namespace_id: u32) -> Option<NsIndex> {
+        self.entries
+            .iter()
+            .position(|entry| entry.namespace_id == namespace_id)
+            .map(|i| NsIndex(i as u32))
+    }
+
+    /// The half-open byte range `[start, end)` a namespace occupies in the
+    /// block payload.
+    pub fn range(&self, index: NsIndex) -> Option<(u32, u32)> {
+        let i = index.0 as usize;
+        let entry = self.entries.get(i)?;
+        let start = match i {
+            0 => 0,
+            _ => self.entries.get(i - 1)?.end_offset,
+        };
+        Some((start, entry.end_offset))
+    }
+
+    pub fn iter(&self) -> NsIter<'a> {
+        NsIter {
+            table: *self,
+            entries: self.entries.iter(),
+            next: 0,
+        }
+    }
+}
+
+/// Iterates over the `(namespace_id, byte_range)` pairs of an [`NsTable`],
+/// reading each entry directly out of the validated buffer.
+pub struct NsIter<'a> {
+    table: NsTable<'a>,
+    entries: RefIter<'a, NsTableEntry>,
+    next: usize,
+}
+
+impl<'a> Iterator for NsIter<'a> {
+    type Item = (u32, (u32, u32));
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        let range = self.table.range(NsIndex(self.next as u32))?;
+        self.next += 1;
+        Some((entry.namespace_id, range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::encode_table;
+
+    #[test]
+    fn finds_namespaces_and_recovers_their_ranges() {
+        let bytes = encode_table(&[(5, 10), (7, 25)]);
+        let table = NsTable::parse(&bytes).unwrap();
+
+        let five = table.find(5).unwrap();
+        assert_eq!(table.range(five), Some((0, 10)));
+
+        let seven = table.find(7).unwrap();
+        assert_eq!(table.range(seven), Some((10, 25)));
+
+        assert_eq!(table.find(9), None);
+    }
+
+    #[test]
+    fn rejects_a_truncated_table() {
+        let mut bytes = encode_table(&[(5, 10)]);
+        bytes.truncate(bytes.len() - 1);
+        assert!(matches!(NsTable::parse(&bytes), Err(NsTableError::Truncated)));
+    }
+}
@@ -0,0 +1,29 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/nitro/blob/master/LICENSE
+
+//! The raw bytes of an Espresso block payload, as delivered by the DA layer,
+//! before it has been split up by namespace.
+
+/// The declared length of a [`Payload`], in bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PayloadByteLen(pub u32);
+
+/// The full, undifferentiated byte payload of an Espresso block.
+#[derive(Clone, Debug)]
+pub struct Payload {
+    bytes: Vec<u8>,
+}
+
+impl Payload {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self { bytes }
+    }
+
+    pub fn byte_len(&self) -> PayloadByteLen {
+        PayloadByteLen(self.bytes.len() as u32)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
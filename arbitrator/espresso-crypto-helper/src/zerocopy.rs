@@ -0,0 +1,137 @@
+// Copyright 2024, Offchain Labs, Inc.
+// For license information, see https://github.com/nitro/blob/master/LICENSE
+
+//! A small zero-copy parsing layer for fixed-width records arriving from the
+//! DA layer. A buffer is validated for length and alignment once, up front,
+//! then viewed in place as a sequence of `T` records - no per-record
+//! allocation, and a short or misaligned buffer is rejected before any
+//! record is read rather than panicking partway through.
+
+use std::{fmt, marker::PhantomData};
+
+#[derive(Debug)]
+pub enum ViewError {
+    Misaligned { len: usize, width: usize },
+}
+
+impl fmt::Display for ViewError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ViewError::Misaligned { len, width } => write!(
+                f,
+                "buffer of {len} bytes is not a whole number of {width}-byte records"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ViewError {}
+
+/// A fixed-width record that can be read out of a byte slice without
+/// allocating.
+pub trait FromBytes: Copy {
+    const WIDTH: usize;
+
+    /// Reads a record from `bytes`, which is guaranteed by [`Ref`] to be
+    /// exactly `WIDTH` bytes long.
+    fn read(bytes: &[u8]) -> Self;
+}
+
+/// A validated, in-place view of a byte slice as a sequence of fixed-width
+/// `T` records.
+#[derive(Clone, Copy)]
+pub struct Ref<'a, T> {
+    bytes: &'a [u8],
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: FromBytes> Ref<'a, T> {
+    /// Validates that `bytes` is a whole number of `T`-width records, then
+    /// returns a view over them with no further allocation or copying.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, ViewError> {
+        if bytes.len() % T::WIDTH != 0 {
+            return Err(ViewError::Misaligned {
+                len: bytes.len(),
+                width: T::WIDTH,
+            });
+        }
+        Ok(Self {
+            bytes,
+            marker: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len() / T::WIDTH
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<T> {
+        if index >= self.len() {
+            return None;
+        }
+        let start = index * T::WIDTH;
+        Some(T::read(&self.bytes[start..start + T::WIDTH]))
+    }
+
+    pub fn iter(&self) -> RefIter<'a, T> {
+        RefIter { view: *self, next: 0 }
+    }
+}
+
+/// Iterates over the records of a [`Ref`], reading each lazily in place.
+pub struct RefIter<'a, T> {
+    view: Ref<'a, T>,
+    next: usize,
+}
+
+impl<'a, T: FromBytes> Iterator for RefIter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        let item = self.view.get(self.next)?;
+        self.next += 1;
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    struct U16Record(u16);
+
+    impl FromBytes for U16Record {
+        const WIDTH: usize = 2;
+
+        fn read(bytes: &[u8]) -> Self {
+            Self(u16::from_be_bytes(bytes.try_into().unwrap()))
+        }
+    }
+
+    #[test]
+    fn views_a_whole_number_of_records() {
+        let bytes = [0, 1, 0, 2, 0, 3];
+        let view = Ref::<U16Record>::new(&bytes).unwrap();
+        assert_eq!(view.len(), 3);
+        assert_eq!(view.get(1), Some(U16Record(2)));
+        assert_eq!(view.get(3), None);
+        assert_eq!(
+            view.iter().collect::<Vec<_>>(),
+            vec![U16Record(1), U16Record(2), U16Record(3)]
+        );
+    }
+
+    #[test]
+    fn rejects_a_buffer_that_is_not_a_whole_number_of_records() {
+        let bytes = [0, 1, 0];
+        assert!(matches!(
+            Ref::<U16Record>::new(&bytes),
+            Err(ViewError::Misaligned { len: 3, width: 2 })
+        ));
+    }
+}
@@ -0,0 +1,121 @@
+// Copyright 2022-2024, Offchain Labs, Inc.
+// For license information, see https://github.com/nitro/blob/master/LICENSE
+
+//! The wavmio hostios only ever need a handful of operations from whatever is
+//! calling them: a stack frame and guest memory to talk to the running wasm,
+//! plus the replay state (globals, inbox messages, preimages) those hostios
+//! read and write. `CallerEnv` pins that surface down to a single trait so
+//! the logic in `wavmio` can be written once and shared by the JIT, which
+//! implements it over `GoStack`, and the in-wasm replay library, which can
+//! implement it over its own arbitrator-side state.
+
+use std::io::BufRead;
+
+use crate::{
+    gostack::{GoStack, Inbox, WasmEnv},
+    transport::Coordinator,
+    wavmio::{Bytes32, PreimageType},
+};
+use parking_lot::MutexGuard;
+
+/// Operations a wavmio hostio needs from its caller, independent of whether
+/// that caller is the JIT's `GoStack` or the in-wasm replay library.
+pub trait CallerEnv {
+    /// Reads a word from the caller's stack frame.
+    fn read_u64(&self, idx: u32) -> u64;
+    /// Writes a word to the caller's stack frame.
+    fn write_u64(&mut self, idx: u32, value: u64);
+
+    /// Reads `len` bytes from guest memory at `ptr`.
+    fn read_slice(&self, ptr: u64, len: u64) -> Vec<u8>;
+    /// Writes `data` to guest memory at `ptr`.
+    fn write_slice(&mut self, ptr: u64, data: &[u8]);
+    /// The size in bytes of the guest's linear memory.
+    fn memory_size(&self) -> u64;
+
+    fn small_globals(&mut self) -> &mut Vec<u64>;
+    fn large_globals(&mut self) -> &mut Vec<Bytes32>;
+    fn sequencer_messages(&mut self) -> &mut Inbox;
+    fn delayed_messages(&mut self) -> &mut Inbox;
+    fn first_too_far(&self) -> u64;
+    fn preimage(&self, key: &(PreimageType, Bytes32)) -> Option<&[u8]>;
+
+    /// Whether this process still needs to fork and bootstrap its replay
+    /// state from a coordinator before serving hostios.
+    fn forks(&self) -> bool;
+    /// Marks bootstrap as done (or, in principle, pending again).
+    fn set_forks(&mut self, forks: bool);
+    /// Where to reach the coordinator that bootstraps a forked replay.
+    fn coordinator(&self) -> &Coordinator;
+    /// Stores the stream a bootstrapped process should keep reading from.
+    fn set_socket(&mut self, socket: Box<dyn BufRead + Send>);
+}
+
+/// The JIT's implementation of `CallerEnv`, bridging a stack frame (`GoStack`)
+/// and the replay state it was given (`WasmEnv`, behind the env's mutex).
+pub struct JitCallerEnv<'a> {
+    pub sp: GoStack,
+    pub env: MutexGuard<'a, WasmEnv>,
+}
+
+impl<'a> CallerEnv for JitCallerEnv<'a> {
+    fn read_u64(&self, idx: u32) -> u64 {
+        self.sp.read_u64(idx)
+    }
+
+    fn write_u64(&mut self, idx: u32, value: u64) {
+        self.sp.write_u64(idx, value)
+    }
+
+    fn read_slice(&self, ptr: u64, len: u64) -> Vec<u8> {
+        self.sp.read_slice(ptr, len)
+    }
+
+    fn write_slice(&mut self, ptr: u64, data: &[u8]) {
+        self.sp.write_slice(ptr, data)
+    }
+
+    fn memory_size(&self) -> u64 {
+        self.sp.memory_size()
+    }
+
+    fn small_globals(&mut self) -> &mut Vec<u64> {
+        &mut self.env.small_globals
+    }
+
+    fn large_globals(&mut self) -> &mut Vec<Bytes32> {
+        &mut self.env.large_globals
+    }
+
+    fn sequencer_messages(&mut self) -> &mut Inbox {
+        &mut self.env.sequencer_messages
+    }
+
+    fn delayed_messages(&mut self) -> &mut Inbox {
+        &mut self.env.delayed_messages
+    }
+
+    fn first_too_far(&self) -> u64 {
+        self.env.first_too_far
+    }
+
+    fn preimage(&self, key: &(PreimageType, Bytes32)) -> Option<&[u8]> {
+        self.env.preimages.get(key).map(Vec::as_slice)
+    }
+
+    fn forks(&self) -> bool {
+        self.env.forks
+    }
+
+    fn set_forks(&mut self, forks: bool) {
+        self.env.forks = forks;
+    }
+
+    fn coordinator(&self) -> &Coordinator {
+        &self.env.coordinator
+    }
+
+    fn set_socket(&mut self, socket: Box<dyn BufRead + Send>) {
+        self.env.socket = std::sync::Arc::new(Some(socket));
+    }
+}
@@ -0,0 +1,313 @@
+// Copyright 2022-2024, Offchain Labs, Inc.
+// For license information, see https://github.com/nitro/blob/master/LICENSE
+
+//! A small, versioned, length-framed codec for the bootstrap stream a forked
+//! replay worker reads from its coordinator. Every message is:
+//!
+//! ```text
+//! [u32 magic][u16 version][u32 body_len][body; body_len bytes][u32 crc32]
+//! ```
+//!
+//! `body_len` is bounds-checked against a configurable maximum before any
+//! allocation happens, and the trailing CRC is verified before the body is
+//! handed back to the caller, so a truncated or corrupted stream is reported
+//! as a typed [`DecodeError`] instead of silently producing a bogus replay
+//! environment.
+
+use std::{
+    fmt, io,
+    io::{BufRead, Read, Write},
+};
+
+use crate::wavmio::Bytes32;
+
+/// Identifies this as a nitro-espresso replay bootstrap frame.
+pub const FRAME_MAGIC: u32 = 0x4E45_5731; // "NEW1"
+
+/// The only wire version this build knows how to decode.
+pub const FRAME_VERSION: u16 = 1;
+
+/// Default cap on `body_len`, overridable per [`FrameCodec`].
+pub const DEFAULT_MAX_BODY_LEN: u32 = 64 * 1024 * 1024;
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Io(io::Error),
+    BadMagic(u32),
+    UnsupportedVersion(u16),
+    BodyTooLarge { len: u32, max: u32 },
+    CrcMismatch { expected: u32, found: u32 },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(err) => write!(f, "i/o error reading frame: {err}"),
+            DecodeError::BadMagic(magic) => write!(f, "bad frame magic {magic:#010x}"),
+            DecodeError::UnsupportedVersion(version) => {
+                write!(f, "unsupported frame version {version}")
+            }
+            DecodeError::BodyTooLarge { len, max } => {
+                write!(f, "frame body len {len} exceeds max {max}")
+            }
+            DecodeError::CrcMismatch { expected, found } => {
+                write!(f, "frame crc mismatch: expected {expected:#010x}, found {found:#010x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<io::Error> for DecodeError {
+    fn from(err: io::Error) -> Self {
+        DecodeError::Io(err)
+    }
+}
+
+/// A decoded, CRC-verified frame. The body is left undecoded so callers can
+/// interpret it according to the message kind they expected.
+#[derive(Debug)]
+pub struct Frame {
+    pub version: u16,
+    pub body: Vec<u8>,
+}
+
+/// Decodes and encodes frames, enforcing a maximum body length so a corrupt
+/// `body_len` field can't be used to force an unbounded allocation.
+pub struct FrameCodec {
+    max_body_len: u32,
+}
+
+impl Default for FrameCodec {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BODY_LEN)
+    }
+}
+
+impl FrameCodec {
+    pub fn new(max_body_len: u32) -> Self {
+        Self { max_body_len }
+    }
+
+    pub fn decode(&self, stream: &mut impl BufRead) -> Result<Frame, DecodeError> {
+        let magic = read_u32(stream)?;
+        if magic != FRAME_MAGIC {
+            return Err(DecodeError::BadMagic(magic));
+        }
+        let version = read_u16(stream)?;
+        if version != FRAME_VERSION {
+            return Err(DecodeError::UnsupportedVersion(version));
+        }
+        let body_len = read_u32(stream)?;
+        if body_len > self.max_body_len {
+            return Err(DecodeError::BodyTooLarge {
+                len: body_len,
+                max: self.max_body_len,
+            });
+        }
+        let mut body = vec![0; body_len as usize];
+        stream.read_exact(&mut body)?;
+
+        let expected = read_u32(stream)?;
+        let found = crc32(&body);
+        if expected != found {
+            return Err(DecodeError::CrcMismatch { expected, found });
+        }
+        Ok(Frame { version, body })
+    }
+
+    pub fn encode(&self, body: &[u8], out: &mut impl Write) -> io::Result<()> {
+        out.write_all(&FRAME_MAGIC.to_be_bytes())?;
+        out.write_all(&FRAME_VERSION.to_be_bytes())?;
+        out.write_all(&(body.len() as u32).to_be_bytes())?;
+        out.write_all(body)?;
+        out.write_all(&crc32(body).to_be_bytes())?;
+        Ok(())
+    }
+}
+
+fn read_u16(stream: &mut impl Read) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    stream.read_exact(&mut buf)?;
+    Ok(u16::from_be_bytes(buf))
+}
+
+fn read_u32(stream: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    stream.read_exact(&mut buf)?;
+    Ok(u32::from_be_bytes(buf))
+}
+
+pub fn read_u64(stream: &mut impl Read) -> io::Result<u64> {
+    let mut buf = [0; 8];
+    stream.read_exact(&mut buf)?;
+    Ok(u64::from_be_bytes(buf))
+}
+
+pub fn read_bytes32(stream: &mut impl Read) -> io::Result<Bytes32> {
+    let mut buf = [0; 32];
+    stream.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// Reads a length-prefixed byte string, rejecting a length beyond `max_len`
+/// before allocating. The prefix is fully attacker/DA-controlled and
+/// independent of the frame's own `body_len` cap, so without this a few
+/// corrupt bytes could claim up to `u64::MAX` and abort the process on the
+/// allocation.
+pub fn read_bytes(stream: &mut impl Read, max_len: u64) -> io::Result<Vec<u8>> {
+    let len = read_u64(stream)?;
+    if len > max_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("record length {len} exceeds remaining frame body ({max_len})"),
+        ));
+    }
+    let mut buf = vec![0; len as usize];
+    stream.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+/// A typed sub-record inside an init frame's body, one per global/inbox
+/// message carried over the bootstrap stream.
+pub enum InitRecord {
+    Globals { small: Vec<u64>, large: Vec<Bytes32> },
+    SequencerMessage { position: u64, data: Vec<u8> },
+    DelayedMessage { position: u64, data: Vec<u8> },
+}
+
+const RECORD_GLOBALS: u8 = 0;
+const RECORD_SEQUENCER_MESSAGE: u8 = 1;
+const RECORD_DELAYED_MESSAGE: u8 = 2;
+const RECORD_END: u8 = 0xff;
+
+impl InitRecord {
+    /// Reads sub-records out of an init frame's body until the `RECORD_END`
+    /// sentinel is hit.
+    pub fn decode_all(body: &[u8]) -> io::Result<Vec<InitRecord>> {
+        let mut stream = body;
+        let mut records = Vec::new();
+        loop {
+            let mut tag = [0; 1];
+            stream.read_exact(&mut tag)?;
+            match tag[0] {
+                RECORD_END => return Ok(records),
+                RECORD_GLOBALS => {
+                    let small_count = read_u64(&mut stream)?;
+                    let small = (0..small_count)
+                        .map(|_| read_u64(&mut stream))
+                        .collect::<io::Result<Vec<_>>>()?;
+                    let large_count = read_u64(&mut stream)?;
+                    let large = (0..large_count)
+                        .map(|_| read_bytes32(&mut stream))
+                        .collect::<io::Result<Vec<_>>>()?;
+                    records.push(InitRecord::Globals { small, large });
+                }
+                RECORD_SEQUENCER_MESSAGE => {
+                    let position = read_u64(&mut stream)?;
+                    let remaining = stream.len() as u64;
+                    let data = read_bytes(&mut stream, remaining)?;
+                    records.push(InitRecord::SequencerMessage { position, data });
+                }
+                RECORD_DELAYED_MESSAGE => {
+                    let position = read_u64(&mut stream)?;
+                    let remaining = stream.len() as u64;
+                    let data = read_bytes(&mut stream, remaining)?;
+                    records.push(InitRecord::DelayedMessage { position, data });
+                }
+                other => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("unknown init record tag {other}"),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+/// CRC-32 (IEEE 802.3), computed byte-at-a-time so this module has no
+/// dependency on an external crc crate.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(body: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        FrameCodec::default().encode(body, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn round_trips_a_frame() {
+        let wire = encode(b"hello");
+        let frame = FrameCodec::default().decode(&mut &wire[..]).unwrap();
+        assert_eq!(frame.version, FRAME_VERSION);
+        assert_eq!(frame.body, b"hello");
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut wire = encode(b"hello");
+        wire[0] ^= 0xff;
+        let err = FrameCodec::default().decode(&mut &wire[..]).unwrap_err();
+        assert!(matches!(err, DecodeError::BadMagic(_)));
+    }
+
+    #[test]
+    fn rejects_unsupported_version() {
+        let mut wire = encode(b"hello");
+        wire[4..6].copy_from_slice(&(FRAME_VERSION + 1).to_be_bytes());
+        let err = FrameCodec::default().decode(&mut &wire[..]).unwrap_err();
+        assert!(matches!(err, DecodeError::UnsupportedVersion(_)));
+    }
+
+    #[test]
+    fn rejects_crc_mismatch() {
+        let mut wire = encode(b"hello");
+        let last = wire.len() - 1;
+        wire[last] ^= 0xff;
+        let err = FrameCodec::default().decode(&mut &wire[..]).unwrap_err();
+        assert!(matches!(err, DecodeError::CrcMismatch { .. }));
+    }
+
+    #[test]
+    fn rejects_oversized_body_len_before_allocating() {
+        let codec = FrameCodec::new(4);
+        let wire = encode(b"hello");
+        let err = codec.decode(&mut &wire[..]).unwrap_err();
+        assert!(matches!(err, DecodeError::BodyTooLarge { len: 5, max: 4 }));
+    }
+
+    #[test]
+    fn read_bytes_rejects_length_beyond_remaining_body() {
+        // A length prefix that claims more than the 3 bytes actually left in
+        // the stream must be rejected instead of allocating for it.
+        let mut body = 100u64.to_be_bytes().to_vec();
+        body.extend_from_slice(b"abc");
+        let err = read_bytes(&mut &body[..], 3).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn decode_all_rejects_a_record_claiming_more_than_the_body_has() {
+        let mut body = vec![RECORD_SEQUENCER_MESSAGE];
+        body.extend_from_slice(&0u64.to_be_bytes()); // position
+        body.extend_from_slice(&u64::MAX.to_be_bytes()); // claimed data length
+        body.extend_from_slice(b"abc");
+        assert!(InitRecord::decode_all(&body).is_err());
+    }
+}
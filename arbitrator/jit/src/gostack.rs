@@ -0,0 +1,144 @@
+// Copyright 2022-2024, Offchain Labs, Inc.
+// For license information, see https://github.com/nitro/blob/master/LICENSE
+
+//! The JIT's view of a running replay: the Go stack frame a hostio was called
+//! with (`GoStack`), and the state that persists across hostio calls for the
+//! lifetime of the process (`WasmEnv`).
+
+use std::{
+    collections::HashMap,
+    io::BufRead,
+    sync::Arc,
+};
+
+use parking_lot::{Mutex, MutexGuard};
+
+use crate::{
+    transport::Coordinator,
+    wavmio::{Bytes32, PreimageType},
+};
+
+/// Inbox messages, keyed by their position.
+pub type Inbox = HashMap<u64, Vec<u8>>;
+
+/// Preimages, keyed by the oracle that produced them and the hash under which
+/// they were requested.
+pub type PreimageMap = HashMap<(PreimageType, Bytes32), Vec<u8>>;
+
+/// A `WasmEnv`, shared between the hostio-calling thread and whatever set it
+/// up (or, after a fork, reinitializes it over `ready_hostio`).
+pub type WasmEnvArc = Arc<Mutex<WasmEnv>>;
+
+/// A stack frame passed to a hostio, plus the guest's linear memory backing
+/// it. `GoStack` owns no state of its own beyond what it needs to read and
+/// write that frame; the replay state it shares a call with lives on
+/// `WasmEnv`.
+pub struct GoStack {
+    sp: u32,
+    memory: Arc<Mutex<Vec<u8>>>,
+}
+
+impl GoStack {
+    /// Splits a raw stack pointer and the env it was called with into a
+    /// `GoStack` and a locked view of the env, so a hostio can work with both
+    /// at once without re-acquiring the lock.
+    pub fn new(sp: u32, env: &WasmEnvArc) -> (GoStack, MutexGuard<'_, WasmEnv>) {
+        let guard = env.lock();
+        let memory = guard.memory.clone();
+        (GoStack { sp, memory }, guard)
+    }
+
+    fn arg_offset(&self, idx: u32) -> usize {
+        self.sp as usize + 8 + idx as usize * 8
+    }
+
+    pub fn read_u64(&self, idx: u32) -> u64 {
+        let memory = self.memory.lock();
+        let offset = self.arg_offset(idx);
+        u64::from_le_bytes(memory[offset..offset + 8].try_into().unwrap())
+    }
+
+    pub fn write_u64(&self, idx: u32, value: u64) {
+        let mut memory = self.memory.lock();
+        let offset = self.arg_offset(idx);
+        memory[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn read_slice(&self, ptr: u64, len: u64) -> Vec<u8> {
+        let memory = self.memory.lock();
+        let start = ptr as usize;
+        let end = start + len as usize;
+        memory[start..end].to_vec()
+    }
+
+    pub fn write_slice(&self, ptr: u64, data: &[u8]) {
+        let mut memory = self.memory.lock();
+        let start = ptr as usize;
+        memory[start..start + data.len()].copy_from_slice(data);
+    }
+
+    pub fn memory_size(&self) -> u64 {
+        self.memory.lock().len() as u64
+    }
+}
+
+/// The replay state that outlives any single hostio call: the global state
+/// the guest is proving over, the inbox messages it may read, and the
+/// preimages it may resolve.
+pub struct WasmEnv {
+    pub forks: bool,
+    pub coordinator: Coordinator,
+    pub small_globals: Vec<u64>,
+    pub large_globals: Vec<Bytes32>,
+    pub sequencer_messages: Inbox,
+    pub delayed_messages: Inbox,
+    pub first_too_far: u64,
+    pub preimages: PreimageMap,
+    pub socket: Arc<Option<Box<dyn BufRead + Send>>>,
+    memory: Arc<Mutex<Vec<u8>>>,
+}
+
+impl Default for WasmEnv {
+    fn default() -> Self {
+        Self {
+            forks: false,
+            coordinator: Coordinator::default(),
+            small_globals: Vec::new(),
+            large_globals: Vec::new(),
+            sequencer_messages: Inbox::new(),
+            delayed_messages: Inbox::new(),
+            first_too_far: 0,
+            preimages: PreimageMap::new(),
+            socket: Arc::new(None),
+            memory: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+/// Something that stops the guest from making further progress: either it
+/// asked to exit, or a hostio hit a condition it can't recover from.
+#[derive(Debug)]
+pub enum Escape {
+    Exit(u32),
+    Failure(String),
+}
+
+/// The result of a hostio call: `Ok(())` to let the guest keep running, or an
+/// `Escape` to stop it.
+pub type MaybeEscape = Result<(), Escape>;
+
+impl Escape {
+    pub fn hostio(message: &str) -> MaybeEscape {
+        Err(Escape::Failure(message.to_string()))
+    }
+
+    pub fn exit(code: u32) -> MaybeEscape {
+        Err(Escape::Exit(code))
+    }
+}
+
+impl From<std::io::Error> for Escape {
+    fn from(err: std::io::Error) -> Self {
+        Escape::Failure(err.to_string())
+    }
+}
@@ -0,0 +1,87 @@
+// Copyright 2022-2024, Offchain Labs, Inc.
+// For license information, see https://github.com/nitro/blob/master/LICENSE
+
+//! How a forked replay worker reaches the process coordinating it. The
+//! default, [`Coordinator::LocalFork`], dials the parent over a loopback TCP
+//! socket the way a single-machine proving run always has. [`Coordinator::WebSocket`]
+//! instead tunnels the same framed init stream through one long-lived
+//! outbound connection, so workers that sit behind NAT (a fleet of proving
+//! machines that can't be dialed into directly) can still be coordinated.
+
+use std::{
+    io::{self, BufRead, BufReader, Read},
+    net::TcpStream,
+};
+
+use tungstenite::{stream::MaybeTlsStream, Message, WebSocket};
+
+/// Where a replay worker should connect to receive its bootstrap frame.
+pub enum Coordinator {
+    /// Connect to the forking parent on this machine over a plain TCP socket.
+    LocalFork,
+    /// Dial a remote coordinator over a WebSocket tunnel.
+    WebSocket { url: String },
+}
+
+impl Default for Coordinator {
+    fn default() -> Self {
+        Coordinator::LocalFork
+    }
+}
+
+impl Coordinator {
+    /// Connects to the coordinator, returning a reader that satisfies the
+    /// same `BufRead` contract the framed codec expects regardless of
+    /// transport.
+    pub fn connect(&self, port: &str) -> io::Result<Box<dyn BufRead + Send>> {
+        match self {
+            Coordinator::LocalFork => {
+                let address = format!("127.0.0.1:{port}");
+                let socket = TcpStream::connect(&address)?;
+                Ok(Box::new(BufReader::new(socket)))
+            }
+            Coordinator::WebSocket { url } => {
+                Ok(Box::new(BufReader::new(WebSocketStream::connect(url)?)))
+            }
+        }
+    }
+}
+
+/// Adapts a WebSocket's binary message stream to `io::Read` so it can be
+/// wrapped in a `BufReader` and fed to the same framed codec a raw TCP socket
+/// uses.
+struct WebSocketStream {
+    socket: WebSocket<MaybeTlsStream<TcpStream>>,
+    pending: io::Cursor<Vec<u8>>,
+}
+
+impl WebSocketStream {
+    fn connect(url: &str) -> io::Result<Self> {
+        let (socket, _response) =
+            tungstenite::connect(url).map_err(|err| io::Error::other(err.to_string()))?;
+        Ok(Self {
+            socket,
+            pending: io::Cursor::new(Vec::new()),
+        })
+    }
+}
+
+impl Read for WebSocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.pending.read(buf)?;
+            if read > 0 {
+                return Ok(read);
+            }
+            match self
+                .socket
+                .read()
+                .map_err(|err| io::Error::other(err.to_string()))?
+            {
+                Message::Binary(data) => self.pending = io::Cursor::new(data),
+                Message::Close(_) => return Ok(0),
+                _ => continue,
+            }
+        }
+    }
+}
@@ -1,59 +1,99 @@
 // Copyright 2022, Offchain Labs, Inc.
 // For license information, see https://github.com/nitro/blob/master/LICENSE
 
-use std::{
-    io,
-    io::{BufReader, ErrorKind},
-    net::TcpStream,
-    sync::Arc,
-};
+use std::io::{self, ErrorKind};
 
-use parking_lot::MutexGuard;
+use espresso_crypto_helper::{NsProof, NsTable};
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
 
 use crate::{
-    gostack::{Escape, GoStack, Inbox, MaybeEscape, WasmEnv, WasmEnvArc},
-    socket,
+    callerenv::{CallerEnv, JitCallerEnv},
+    codec::{FrameCodec, InitRecord},
+    gostack::{Escape, GoStack, MaybeEscape, WasmEnvArc},
 };
 
 pub type Bytes32 = [u8; 32];
 
+/// Identifies which oracle a preimage hash is drawn from, so the same 32-byte
+/// key space can be shared by multiple hash functions without collisions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PreimageType {
+    Keccak256,
+    Sha2_256,
+    EthVersionedHash,
+}
+
+impl PreimageType {
+    fn try_from_u64(ty: u64) -> Option<Self> {
+        match ty {
+            0 => Some(Self::Keccak256),
+            1 => Some(Self::Sha2_256),
+            2 => Some(Self::EthVersionedHash),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Keccak256 => "keccak256",
+            Self::Sha2_256 => "sha2-256",
+            Self::EthVersionedHash => "EIP-4844 versioned hash",
+        }
+    }
+}
+
+/// The EIP-4844 "blob versioned hash" always begins with this version byte.
+const BLOB_COMMITMENT_VERSION_KZG: u8 = 0x01;
+
+/// The length in bytes of a KZG commitment, as used by EIP-4844.
+const KZG_COMMITMENT_LEN: usize = 48;
+
 pub fn get_global_state_bytes32(env: &WasmEnvArc, sp: u32) -> MaybeEscape {
-    let (sp, mut env) = GoStack::new(sp, env);
-    ready_hostio(&mut *env)?;
+    let (sp, env) = GoStack::new(sp, env);
+    let mut caller = JitCallerEnv { sp, env };
+    ready_hostio(&mut caller)?;
+    get_global_state_bytes32_impl(&mut caller)
+}
 
-    let global = sp.read_u64(0) as u32 as usize;
-    let out_ptr = sp.read_u64(1);
-    let mut out_len = sp.read_u64(2) as usize;
+fn get_global_state_bytes32_impl(caller: &mut impl CallerEnv) -> MaybeEscape {
+    let global = caller.read_u64(0) as u32 as usize;
+    let out_ptr = caller.read_u64(1);
+    let mut out_len = caller.read_u64(2) as usize;
     if out_len < 32 {
         eprintln!("Go trying to read block hash into {out_len} bytes long buffer");
     } else {
         out_len = 32;
     }
 
-    let global = match env.large_globals.get(global) {
-        Some(global) => global,
+    let global = match caller.large_globals().get(global) {
+        Some(global) => *global,
         None => return Escape::hostio("global read out of bounds in wavmio.getGlobalStateBytes32"),
     };
-    sp.write_slice(out_ptr, &global[..out_len]);
+    caller.write_slice(out_ptr, &global[..out_len]);
     Ok(())
 }
 
 pub fn set_global_state_bytes32(env: &WasmEnvArc, sp: u32) -> MaybeEscape {
-    let (sp, mut env) = GoStack::new(sp, env);
-    ready_hostio(&mut *env)?;
+    let (sp, env) = GoStack::new(sp, env);
+    let mut caller = JitCallerEnv { sp, env };
+    ready_hostio(&mut caller)?;
+    set_global_state_bytes32_impl(&mut caller)
+}
 
-    let global = sp.read_u64(0) as u32 as usize;
-    let src_ptr = sp.read_u64(1);
-    let src_len = sp.read_u64(2);
+fn set_global_state_bytes32_impl(caller: &mut impl CallerEnv) -> MaybeEscape {
+    let global = caller.read_u64(0) as u32 as usize;
+    let src_ptr = caller.read_u64(1);
+    let src_len = caller.read_u64(2);
     if src_len != 32 {
         eprintln!("Go trying to set 32-byte global with a {src_len} bytes long buffer");
         return Ok(());
     }
 
-    let slice = sp.read_slice(src_ptr, src_len);
-    let slice = &slice.try_into().unwrap();
-    match env.large_globals.get_mut(global) {
-        Some(global) => *global = *slice,
+    let slice = caller.read_slice(src_ptr, src_len);
+    let slice: Bytes32 = slice.try_into().unwrap();
+    match caller.large_globals().get_mut(global) {
+        Some(global) => *global = slice,
         None => {
             return Escape::hostio("global write out of bounds in wavmio.setGlobalStateBytes32")
         }
@@ -62,60 +102,65 @@ pub fn set_global_state_bytes32(env: &WasmEnvArc, sp: u32) -> MaybeEscape {
 }
 
 pub fn get_global_state_u64(env: &WasmEnvArc, sp: u32) -> MaybeEscape {
-    let (sp, mut env) = GoStack::new(sp, env);
-    ready_hostio(&mut *env)?;
+    let (sp, env) = GoStack::new(sp, env);
+    let mut caller = JitCallerEnv { sp, env };
+    ready_hostio(&mut caller)?;
+    get_global_state_u64_impl(&mut caller)
+}
 
-    let global = sp.read_u64(0) as u32 as usize;
-    match env.small_globals.get(global) {
-        Some(global) => sp.write_u64(1, *global),
+fn get_global_state_u64_impl(caller: &mut impl CallerEnv) -> MaybeEscape {
+    let global = caller.read_u64(0) as u32 as usize;
+    match caller.small_globals().get(global) {
+        Some(global) => {
+            let global = *global;
+            caller.write_u64(1, global);
+        }
         None => return Escape::hostio("global read out of bounds in wavmio.getGlobalStateU64"),
     }
     Ok(())
 }
 
 pub fn set_global_state_u64(env: &WasmEnvArc, sp: u32) -> MaybeEscape {
-    let (sp, mut env) = GoStack::new(sp, env);
-    ready_hostio(&mut *env)?;
+    let (sp, env) = GoStack::new(sp, env);
+    let mut caller = JitCallerEnv { sp, env };
+    ready_hostio(&mut caller)?;
+    set_global_state_u64_impl(&mut caller)
+}
 
-    let global = sp.read_u64(0) as u32 as usize;
-    match env.small_globals.get_mut(global) {
-        Some(global) => *global = sp.read_u64(1),
+fn set_global_state_u64_impl(caller: &mut impl CallerEnv) -> MaybeEscape {
+    let global = caller.read_u64(0) as u32 as usize;
+    let value = caller.read_u64(1);
+    match caller.small_globals().get_mut(global) {
+        Some(global) => *global = value,
         None => return Escape::hostio("global write out of bounds in wavmio.setGlobalStateU64"),
     }
     Ok(())
 }
 
 pub fn read_inbox_message(env: &WasmEnvArc, sp: u32) -> MaybeEscape {
-    let (sp, mut env) = GoStack::new(sp, env);
-    ready_hostio(&mut *env)?;
-
-    let inbox = &env.sequencer_messages;
-    inbox_message_impl(&sp, &env, inbox, "wavmio.readInboxMessage")
+    let (sp, env) = GoStack::new(sp, env);
+    let mut caller = JitCallerEnv { sp, env };
+    ready_hostio(&mut caller)?;
+    inbox_message_impl(&mut caller, false, "wavmio.readInboxMessage")
 }
 
 pub fn read_delayed_inbox_message(env: &WasmEnvArc, sp: u32) -> MaybeEscape {
-    let (sp, mut env) = GoStack::new(sp, env);
-    ready_hostio(&mut *env)?;
-
-    let inbox = &env.delayed_messages;
-    inbox_message_impl(&sp, &env, inbox, "wavmio.readDelayedInboxMessage")
+    let (sp, env) = GoStack::new(sp, env);
+    let mut caller = JitCallerEnv { sp, env };
+    ready_hostio(&mut caller)?;
+    inbox_message_impl(&mut caller, true, "wavmio.readDelayedInboxMessage")
 }
 
 /// Reads an inbox message
 /// note: the order of the checks is very important.
-fn inbox_message_impl(
-    sp: &GoStack,
-    env: &MutexGuard<WasmEnv>,
-    inbox: &Inbox,
-    name: &str,
-) -> MaybeEscape {
-    let msg_num = sp.read_u64(0);
-    let offset = sp.read_u64(1);
-    let out_ptr = sp.read_u64(2);
-    let out_len = sp.read_u64(3);
+fn inbox_message_impl(caller: &mut impl CallerEnv, delayed: bool, name: &str) -> MaybeEscape {
+    let msg_num = caller.read_u64(0);
+    let offset = caller.read_u64(1);
+    let out_ptr = caller.read_u64(2);
+    let out_len = caller.read_u64(3);
     if out_len != 32 {
         eprintln!("Go trying to read inbox message with out len {out_len} in {name}");
-        sp.write_u64(5, 0);
+        caller.write_u64(5, 0);
         return Ok(());
     }
 
@@ -126,16 +171,20 @@ fn inbox_message_impl(
         }};
     }
 
-    let too_far = env.first_too_far;
+    let too_far = caller.first_too_far();
+    let inbox = match delayed {
+        true => caller.delayed_messages(),
+        false => caller.sequencer_messages(),
+    };
     let message = match inbox.get(&msg_num) {
-        Some(message) => message,
+        Some(message) => message.clone(),
         None => match msg_num < too_far {
             true => error!("missing inbox message {msg_num} of {too_far} in {name}"),
             false => error!("message {msg_num} of {too_far} too far in {name}"),
         },
     };
 
-    if out_ptr + 32 > sp.memory_size() {
+    if out_ptr + 32 > caller.memory_size() {
         error!("unknown message type in {name}");
     }
     let offset = match u32::try_from(offset) {
@@ -145,23 +194,29 @@ fn inbox_message_impl(
 
     let len = std::cmp::min(32, message.len().saturating_sub(offset)) as usize;
     let read = message.get(offset..(offset + len)).unwrap_or_default();
-    sp.write_slice(out_ptr, &read);
-    sp.write_u64(5, read.len() as u64);
+    caller.write_slice(out_ptr, read);
+    caller.write_u64(5, read.len() as u64);
     Ok(())
 }
 
 pub fn resolve_preimage(env: &WasmEnvArc, sp: u32) -> MaybeEscape {
     let (sp, env) = GoStack::new(sp, env);
+    let mut caller = JitCallerEnv { sp, env };
+    resolve_preimage_impl(&mut caller)
+}
+
+fn resolve_preimage_impl(caller: &mut impl CallerEnv) -> MaybeEscape {
     let name = "wavmio.resolvePreImage";
 
-    let hash_ptr = sp.read_u64(0);
-    let hash_len = sp.read_u64(1);
-    let offset = sp.read_u64(3);
-    let out_ptr = sp.read_u64(4);
-    let out_len = sp.read_u64(5);
+    let hash_ptr = caller.read_u64(0);
+    let hash_len = caller.read_u64(1);
+    let preimage_ty = caller.read_u64(2);
+    let offset = caller.read_u64(3);
+    let out_ptr = caller.read_u64(4);
+    let out_len = caller.read_u64(5);
     if hash_len != 32 || out_len != 32 {
         eprintln!("Go trying to resolve pre image with hash len {hash_len} and out len {out_len}");
-        sp.write_u64(7, 0);
+        caller.write_u64(7, 0);
         return Ok(());
     }
 
@@ -172,29 +227,158 @@ pub fn resolve_preimage(env: &WasmEnvArc, sp: u32) -> MaybeEscape {
         }};
     }
 
-    let hash = sp.read_slice(hash_ptr, hash_len);
-    let hash: &[u8; 32] = &hash.try_into().unwrap();
-    let preimage = match env.preimages.get(hash) {
-        Some(preimage) => preimage,
+    let preimage_ty = match PreimageType::try_from_u64(preimage_ty) {
+        Some(ty) => ty,
+        None => error!("unknown preimage type {preimage_ty} in {name}"),
+    };
+
+    let hash = caller.read_slice(hash_ptr, hash_len);
+    let hash: Bytes32 = hash.try_into().unwrap();
+    let preimage = match caller.preimage(&(preimage_ty, hash)) {
+        Some(preimage) => preimage.to_vec(),
         None => error!(
-            "Missing requested preimage for hash {} in {name}",
+            "Missing requested {} preimage for hash {} in {name}",
+            preimage_ty.name(),
             hex::encode(hash)
         ),
     };
+    verify_preimage(preimage_ty, &hash, &preimage, name)?;
+    // For a versioned hash, `preimage` is the KZG commitment the hash was
+    // checked against followed by the blob itself; the guest only ever asks
+    // for the blob, so the commitment prefix isn't part of the offset space.
+    let payload = match preimage_ty {
+        PreimageType::EthVersionedHash => &preimage[KZG_COMMITMENT_LEN..],
+        _ => preimage.as_slice(),
+    };
     let offset = match u32::try_from(offset) {
         Ok(offset) => offset as usize,
         Err(_) => error!("bad offset {offset} in {name}"),
     };
 
-    let len = std::cmp::min(32, preimage.len().saturating_sub(offset)) as usize;
-    let read = preimage.get(offset..(offset + len)).unwrap_or_default();
-    sp.write_slice(out_ptr, &read);
-    sp.write_u64(7, read.len() as u64);
+    let len = std::cmp::min(32, payload.len().saturating_sub(offset)) as usize;
+    let read = payload.get(offset..(offset + len)).unwrap_or_default();
+    caller.write_slice(out_ptr, read);
+    caller.write_u64(7, read.len() as u64);
     Ok(())
 }
 
-fn ready_hostio(env: &mut WasmEnv) -> MaybeEscape {
-    if !env.forks {
+/// Confirms a preimage actually hashes to the key it was stored under, per the
+/// rules of its oracle's hash function.
+fn verify_preimage(ty: PreimageType, hash: &Bytes32, preimage: &[u8], name: &str) -> MaybeEscape {
+    macro_rules! error {
+        ($text:expr $(,$args:expr)*) => {{
+            let text = format!($text $(,$args)*);
+            return Escape::hostio(&text)
+        }};
+    }
+
+    match ty {
+        PreimageType::Keccak256 => {
+            let have: Bytes32 = Keccak256::digest(preimage).into();
+            if &have != hash {
+                error!("{} preimage does not match its hash in {name}", ty.name());
+            }
+        }
+        PreimageType::Sha2_256 => {
+            let have: Bytes32 = Sha256::digest(preimage).into();
+            if &have != hash {
+                error!("{} preimage does not match its hash in {name}", ty.name());
+            }
+        }
+        PreimageType::EthVersionedHash => {
+            if hash[0] != BLOB_COMMITMENT_VERSION_KZG {
+                error!(
+                    "{} hash does not start with the KZG version byte in {name}",
+                    ty.name()
+                );
+            }
+            // Per EIP-4844, a versioned hash commits to the blob's KZG
+            // commitment, not to the blob's raw bytes, so only the leading
+            // commitment is hashed here; the blob bytes that follow it are
+            // what the guest actually reads back from `resolve_preimage`.
+            if preimage.len() < KZG_COMMITMENT_LEN {
+                error!(
+                    "{} preimage is shorter than a KZG commitment in {name}",
+                    ty.name()
+                );
+            }
+            let commitment = &preimage[..KZG_COMMITMENT_LEN];
+            let have: Bytes32 = Sha256::digest(commitment).into();
+            if have[1..] != hash[1..] {
+                error!("{} commitment does not match its hash in {name}", ty.name());
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn verify_namespace_proof(env: &WasmEnvArc, sp: u32) -> MaybeEscape {
+    let (sp, env) = GoStack::new(sp, env);
+    let mut caller = JitCallerEnv { sp, env };
+    verify_namespace_proof_impl(&mut caller)
+}
+
+/// Checks that the inbox payload the guest was handed really is the
+/// namespace data Espresso DA committed to, and tells the guest where in the
+/// payload that namespace's bytes live.
+fn verify_namespace_proof_impl(caller: &mut impl CallerEnv) -> MaybeEscape {
+    let name = "wavmio.verifyNamespaceProof";
+
+    let namespace_id = caller.read_u64(0) as u32;
+    let table_ptr = caller.read_u64(1);
+    let table_len = caller.read_u64(2);
+    let proof_ptr = caller.read_u64(3);
+    let proof_len = caller.read_u64(4);
+    let commitment_ptr = caller.read_u64(5);
+    let out_offset = 6;
+    let out_len = 7;
+    let out_success = 8;
+
+    macro_rules! error {
+        ($text:expr $(,$args:expr)*) => {{
+            let text = format!($text $(,$args)*);
+            return Escape::hostio(&text)
+        }};
+    }
+
+    let memory_size = caller.memory_size();
+    if table_ptr + table_len > memory_size {
+        error!("table out of bounds in {name}");
+    }
+    if proof_ptr + proof_len > memory_size {
+        error!("proof out of bounds in {name}");
+    }
+    if commitment_ptr + 32 > memory_size {
+        error!("commitment out of bounds in {name}");
+    }
+
+    let table_bytes = caller.read_slice(table_ptr, table_len);
+    let table = match NsTable::parse(&table_bytes) {
+        Ok(table) => table,
+        Err(err) => error!("bad namespace table in {name}: {err}"),
+    };
+
+    let proof_bytes = caller.read_slice(proof_ptr, proof_len);
+    let proof = match NsProof::parse(&proof_bytes) {
+        Ok(proof) => proof,
+        Err(err) => error!("bad namespace proof in {name}: {err}"),
+    };
+
+    let commitment: Bytes32 = caller.read_slice(commitment_ptr, 32).try_into().unwrap();
+
+    let (offset, end) = match proof.verify(&table, namespace_id, &commitment) {
+        Ok(range) => range,
+        Err(err) => error!("namespace proof for namespace {namespace_id} failed in {name}: {err}"),
+    };
+
+    caller.write_u64(out_offset, offset as u64);
+    caller.write_u64(out_len, end.saturating_sub(offset) as u64);
+    caller.write_u64(out_success, 1);
+    Ok(())
+}
+
+fn ready_hostio(caller: &mut impl CallerEnv) -> MaybeEscape {
+    if !caller.forks() {
         return Ok(());
     }
 
@@ -218,38 +402,101 @@ fn ready_hostio(env: &mut WasmEnv) -> MaybeEscape {
         }
     }
 
-    let address = format!("127.0.0.1:{port}");
-    let socket = TcpStream::connect(&address)?;
+    let mut reader = caller.coordinator().connect(&port)?;
+
+    let frame = match FrameCodec::default().decode(&mut reader) {
+        Ok(frame) => frame,
+        Err(error) => return Escape::hostio(&format!("bad wavmio init frame: {error}")),
+    };
+    let records = match InitRecord::decode_all(&frame.body) {
+        Ok(records) => records,
+        Err(error) => return Escape::hostio(&format!("bad wavmio init record: {error}")),
+    };
+
+    caller.sequencer_messages().clear();
+    caller.delayed_messages().clear();
+
+    for record in records {
+        match record {
+            InitRecord::Globals { small, large } => {
+                *caller.small_globals() = small;
+                *caller.large_globals() = large;
+            }
+            InitRecord::SequencerMessage { position, data } => {
+                caller.sequencer_messages().insert(position, data);
+            }
+            InitRecord::DelayedMessage { position, data } => {
+                caller.delayed_messages().insert(position, data);
+            }
+        }
+    }
+
+    caller.set_socket(reader);
+    caller.set_forks(false);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let mut reader = BufReader::new(socket);
-    let stream = &mut reader;
+    #[test]
+    fn verifies_keccak_preimage() {
+        let preimage = b"hello world".to_vec();
+        let hash: Bytes32 = Keccak256::digest(&preimage).into();
+        assert!(verify_preimage(PreimageType::Keccak256, &hash, &preimage, "test").is_ok());
+    }
 
-    let inbox_position = socket::read_u64(stream)?;
-    let position_within_message = socket::read_u64(stream)?;
-    let last_block_hash = socket::read_bytes32(stream)?;
-    let last_send_root = socket::read_bytes32(stream)?;
+    #[test]
+    fn rejects_mismatched_keccak_preimage() {
+        let preimage = b"hello world".to_vec();
+        let mut hash: Bytes32 = Keccak256::digest(&preimage).into();
+        hash[0] ^= 0xff;
+        assert!(verify_preimage(PreimageType::Keccak256, &hash, &preimage, "test").is_err());
+    }
 
-    env.small_globals = vec![inbox_position, position_within_message];
-    env.large_globals = vec![last_block_hash, last_send_root];
+    #[test]
+    fn verifies_sha2_preimage() {
+        let preimage = b"hello world".to_vec();
+        let hash: Bytes32 = Sha256::digest(&preimage).into();
+        assert!(verify_preimage(PreimageType::Sha2_256, &hash, &preimage, "test").is_ok());
+    }
 
-    env.sequencer_messages.clear();
-    env.delayed_messages.clear();
+    #[test]
+    fn rejects_mismatched_sha2_preimage() {
+        let preimage = b"hello world".to_vec();
+        let mut hash: Bytes32 = Sha256::digest(&preimage).into();
+        hash[0] ^= 0xff;
+        assert!(verify_preimage(PreimageType::Sha2_256, &hash, &preimage, "test").is_err());
+    }
 
-    let mut inbox_position = inbox_position;
-    let mut delayed_position = socket::read_u64(stream)?;
+    /// Builds a versioned-hash preimage: a (fake) KZG commitment followed by
+    /// blob bytes, plus the hash it should verify against.
+    fn blob_preimage(commitment: [u8; KZG_COMMITMENT_LEN], blob: &[u8]) -> (Bytes32, Vec<u8>) {
+        let mut hash: Bytes32 = Sha256::digest(commitment).into();
+        hash[0] = BLOB_COMMITMENT_VERSION_KZG;
+        let mut preimage = commitment.to_vec();
+        preimage.extend_from_slice(blob);
+        (hash, preimage)
+    }
 
-    while socket::read_u8(stream)? == 1 {
-        let message = socket::read_bytes(stream)?;
-        env.sequencer_messages.insert(inbox_position, message);
-        inbox_position += 1;
+    #[test]
+    fn verifies_blob_preimage_against_its_commitment() {
+        let (hash, preimage) = blob_preimage([7; KZG_COMMITMENT_LEN], b"blob data");
+        assert!(verify_preimage(PreimageType::EthVersionedHash, &hash, &preimage, "test").is_ok());
     }
-    while socket::read_u8(stream)? == 1 {
-        let message = socket::read_bytes(stream)?;
-        env.delayed_messages.insert(delayed_position, message);
-        delayed_position += 1;
+
+    #[test]
+    fn rejects_blob_preimage_without_kzg_version_byte() {
+        let (mut hash, preimage) = blob_preimage([7; KZG_COMMITMENT_LEN], b"blob data");
+        hash[0] = 0x00;
+        assert!(verify_preimage(PreimageType::EthVersionedHash, &hash, &preimage, "test").is_err());
     }
 
-    env.socket = Arc::new(Some(reader));
-    env.forks = false;
-    Ok(())
+    #[test]
+    fn rejects_blob_preimage_with_wrong_commitment() {
+        let (hash, preimage) = blob_preimage([7; KZG_COMMITMENT_LEN], b"blob data");
+        let (_, tampered) = blob_preimage([8; KZG_COMMITMENT_LEN], b"blob data");
+        assert!(verify_preimage(PreimageType::EthVersionedHash, &hash, &tampered, "test").is_err());
+    }
 }